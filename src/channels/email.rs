@@ -1,17 +1,82 @@
 use crate::channels::traits::{Channel, ChannelMessage};
 use async_trait::async_trait;
-use lettre::message::Mailbox;
-use lettre::message::MultiPart;
-use lettre::transport::smtp::authentication::Credentials;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine as _;
+use imap::Authenticator;
+use lettre::message::{Attachment, ContentType, Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
 use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use mailparse::MailAddr;
 use mailparse::MailHeaderMap;
 use pulldown_cmark::{html, Options, Parser};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::future::Future;
+use std::io::{BufRead, Write};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 pub const EMAIL_REPLY_META_SEP: &str = "\u{001F}";
 
+/// How `EmailChannel::listen` waits for new mail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmailPollMode {
+    /// Hold an IMAP IDLE session open and wake as soon as the server
+    /// reports new/changed messages. Falls back to `Poll` when the
+    /// server doesn't advertise the `IDLE` capability.
+    Idle,
+    /// Run the configured `search_query` every `poll_interval_secs`,
+    /// reusing the cached IMAP session between polls.
+    Poll,
+}
+
+impl Default for EmailPollMode {
+    fn default() -> Self {
+        EmailPollMode::Poll
+    }
+}
+
+/// `idle.wait_keepalive()` blocks for at most this long before returning
+/// (either because new mail arrived or this interval elapsed), at which
+/// point `idle_listen_blocking`'s loop re-checks its cancellation flag.
+/// This bounds shutdown latency, so it's kept in the seconds range rather
+/// than the ~20-30 minutes most IMAP servers tolerate before dropping an
+/// idle connection — we simply re-issue IDLE far more often than that
+/// requires.
+const IDLE_RENEW_INTERVAL: Duration = Duration::from_secs(15);
+
+type ImapSession = imap::Session<native_tls::TlsStream<std::net::TcpStream>>;
+
+/// A small connection state machine so `EmailChannel` can reuse one IMAP
+/// session across polls/IDLE cycles instead of reconnecting every time.
+/// `Selected` means login *and* `SELECT <inbox_folder>` have both already
+/// happened on this socket, so callers can go straight to `SEARCH`/`FETCH`.
+///
+/// This still sits on the blocking `imap` crate, not an async client: the
+/// session is held across calls, but each call into it still runs inside
+/// its own `tokio::task::spawn_blocking` (see `poll_unseen_blocking`,
+/// `idle_listen_blocking`). What this removes is the repeated
+/// login/`SELECT` handshake per cycle, not the `spawn_blocking` hop
+/// itself — an async IMAP client would be a separate, larger migration.
+enum ImapConnState {
+    Disconnected,
+    Selected(ImapSession),
+}
+
+struct ImapConnection {
+    state: ImapConnState,
+}
+
+impl ImapConnection {
+    fn new() -> Self {
+        Self {
+            state: ImapConnState::Disconnected,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct EmailThreadMeta {
     message_id: Option<String>,
@@ -24,6 +89,169 @@ struct InboundEmail {
     sender: String,
     content: String,
     thread: EmailThreadMeta,
+    attachments: Vec<EmailAttachment>,
+}
+
+/// A file carried by an inbound or outbound email, either an explicit
+/// attachment or a named inline part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The result of walking a message's MIME tree: the best text body we
+/// could find, plus every part that looked like an attachment.
+#[derive(Debug, Clone, Default)]
+struct MimeWalkResult {
+    plain_text: Option<String>,
+    html_text: Option<String>,
+    attachments: Vec<EmailAttachment>,
+}
+
+/// A future resolving to a fresh OAuth2 access token, so callers can
+/// refresh expired tokens instead of handing over a single static one.
+pub type OAuthTokenFuture = Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>>;
+pub type OAuthTokenProvider = Arc<dyn Fn() -> OAuthTokenFuture + Send + Sync>;
+
+/// How `EmailChannel` authenticates against IMAP/SMTP.
+#[derive(Clone)]
+pub enum AuthMethod {
+    /// Plain username/password login (IMAP `LOGIN`, SMTP `PLAIN`/`LOGIN`).
+    Password(String),
+    /// OAuth2 bearer auth (IMAP `XOAUTH2`, SMTP `XOAUTH2` mechanism). The
+    /// provider is invoked on every (re)connect so expired tokens can be
+    /// refreshed transparently.
+    OAuth2 {
+        access_token_provider: OAuthTokenProvider,
+    },
+}
+
+/// Builds the SASL XOAUTH2 initial-response string and hands it to the
+/// `imap` crate's `authenticate` flow.
+struct XOAuth2Authenticator {
+    login: String,
+    access_token: String,
+}
+
+impl Authenticator for XOAuth2Authenticator {
+    type Response = String;
+
+    fn process(&self, _challenge: &[u8]) -> Self::Response {
+        // `imap`'s authenticate() base64-decodes the server challenge
+        // before calling us and base64-*encodes* whatever we return before
+        // writing it to the wire, so this must be the raw SASL response.
+        format!(
+            "user={}\x01auth=Bearer {}\x01\x01",
+            self.login, self.access_token
+        )
+    }
+}
+
+/// Credentials for the optional ManageSieve (RFC 5804) connection used to
+/// push server-side filtering so unwanted mail never reaches `listen`.
+#[derive(Debug, Clone)]
+pub struct ManageSieveConfig {
+    pub host: String,
+    pub port: u16,
+    pub login: String,
+    pub password: String,
+}
+
+const MANAGESIEVE_SCRIPT_NAME: &str = "zeroclaw-allowlist";
+
+/// A composable IMAP `SEARCH` expression, so operators can scope `listen`
+/// to a subset of the mailbox (e.g. "unseen since yesterday, from a given
+/// domain") instead of always scanning every unseen message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchCriterion {
+    Unseen,
+    Unflagged,
+    /// `SINCE <date>`; `date` must already be in IMAP date format, e.g. `01-Jan-2024`.
+    Since(String),
+    From(String),
+    Subject(String),
+    And(Vec<SearchCriterion>),
+    Or(Box<SearchCriterion>, Box<SearchCriterion>),
+    Not(Box<SearchCriterion>),
+}
+
+impl Default for SearchCriterion {
+    fn default() -> Self {
+        SearchCriterion::Unseen
+    }
+}
+
+impl SearchCriterion {
+    /// IMAP SEARCH string literals are space/quote delimited; reject
+    /// values that could otherwise smuggle extra search keys in.
+    fn validate_atom(value: &str) -> anyhow::Result<()> {
+        if value.trim().is_empty() || value.contains(['\r', '\n', '"']) {
+            anyhow::bail!("invalid IMAP SEARCH value: {value:?}");
+        }
+        Ok(())
+    }
+
+    /// `SINCE` is rendered unquoted, so unlike `validate_atom` it must also
+    /// reject embedded whitespace — otherwise a value like `"01-Jan-2024
+    /// UNFLAGGED"` would smuggle in an extra top-level search key.
+    fn validate_date_atom(value: &str) -> anyhow::Result<()> {
+        Self::validate_atom(value)?;
+        if value.split_whitespace().count() != 1 {
+            anyhow::bail!("invalid IMAP SEARCH date: {value:?}");
+        }
+        Ok(())
+    }
+
+    /// `And` renders as a bare space-joined list of keys with no delimiting
+    /// keyword, so it must be parenthesized wherever it isn't already the
+    /// outermost criterion — otherwise `Or`/`Not` (which each consume a
+    /// fixed number of following keys) silently swallow only its first
+    /// child and leave the rest as stray top-level keys. `Or` and `Not`
+    /// don't need this: they always consume exactly the keys that follow
+    /// them, so they're self-delimiting even when nested.
+    fn render_operand(&self) -> anyhow::Result<String> {
+        let rendered = self.render()?;
+        if matches!(self, SearchCriterion::And(_)) {
+            Ok(format!("({rendered})"))
+        } else {
+            Ok(rendered)
+        }
+    }
+
+    fn render(&self) -> anyhow::Result<String> {
+        Ok(match self {
+            SearchCriterion::Unseen => "UNSEEN".to_string(),
+            SearchCriterion::Unflagged => "UNFLAGGED".to_string(),
+            SearchCriterion::Since(date) => {
+                Self::validate_date_atom(date)?;
+                format!("SINCE {date}")
+            }
+            SearchCriterion::From(addr) => {
+                Self::validate_atom(addr)?;
+                format!("FROM \"{addr}\"")
+            }
+            SearchCriterion::Subject(subject) => {
+                Self::validate_atom(subject)?;
+                format!("SUBJECT \"{subject}\"")
+            }
+            SearchCriterion::And(criteria) => {
+                if criteria.is_empty() {
+                    anyhow::bail!("AND requires at least one search criterion");
+                }
+                criteria
+                    .iter()
+                    .map(SearchCriterion::render_operand)
+                    .collect::<anyhow::Result<Vec<_>>>()?
+                    .join(" ")
+            }
+            SearchCriterion::Or(left, right) => {
+                format!("OR {} {}", left.render_operand()?, right.render_operand()?)
+            }
+            SearchCriterion::Not(inner) => format!("NOT {}", inner.render_operand()?),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -31,17 +259,22 @@ pub struct EmailChannel {
     imap_host: String,
     imap_port: u16,
     imap_login: String,
-    imap_password: String,
+    imap_auth: AuthMethod,
     imap_starttls: bool,
     smtp_host: String,
     smtp_port: u16,
     smtp_login: String,
-    smtp_password: String,
+    smtp_auth: AuthMethod,
     smtp_starttls: bool,
     from_address: String,
     inbox_folder: String,
+    sent_folder: Option<String>,
     poll_interval_secs: u64,
+    poll_mode: EmailPollMode,
     allowed_senders: Vec<String>,
+    managesieve: Option<ManageSieveConfig>,
+    search_query: SearchCriterion,
+    imap_conn: Arc<std::sync::Mutex<ImapConnection>>,
 }
 
 impl EmailChannel {
@@ -50,36 +283,53 @@ impl EmailChannel {
         imap_host: String,
         imap_port: u16,
         imap_login: String,
-        imap_password: String,
+        imap_auth: AuthMethod,
         imap_starttls: bool,
         smtp_host: String,
         smtp_port: u16,
         smtp_login: String,
-        smtp_password: String,
+        smtp_auth: AuthMethod,
         smtp_starttls: bool,
         from_address: String,
         inbox_folder: String,
+        sent_folder: Option<String>,
         poll_interval_secs: u64,
+        poll_mode: EmailPollMode,
         allowed_senders: Vec<String>,
+        managesieve: Option<ManageSieveConfig>,
+        search_query: SearchCriterion,
     ) -> Self {
         Self {
             imap_host,
             imap_port,
             imap_login,
-            imap_password,
+            imap_auth,
             imap_starttls,
             smtp_host,
             smtp_port,
             smtp_login,
-            smtp_password,
+            smtp_auth,
             smtp_starttls,
             from_address,
             inbox_folder,
+            sent_folder,
             poll_interval_secs,
+            poll_mode,
             allowed_senders,
+            managesieve,
+            search_query,
+            imap_conn: Arc::new(std::sync::Mutex::new(ImapConnection::new())),
         }
     }
 
+    async fn resolve_oauth_token(provider: &OAuthTokenProvider) -> anyhow::Result<String> {
+        (provider)().await
+    }
+
+    fn resolve_oauth_token_blocking(provider: &OAuthTokenProvider) -> anyhow::Result<String> {
+        tokio::runtime::Handle::current().block_on((provider)())
+    }
+
     fn is_sender_allowed(&self, sender: &str) -> bool {
         if self.allowed_senders.iter().any(|u| u == "*") {
             return true;
@@ -114,28 +364,109 @@ impl EmailChannel {
         None
     }
 
-    fn parse_text_body(raw_email: &[u8]) -> Option<String> {
-        let parsed = mailparse::parse_mail(raw_email).ok()?;
+    /// The file name of `part`: its `Content-Disposition` `filename`
+    /// parameter, or else its `Content-Type` `name` parameter.
+    fn part_filename(part: &mailparse::ParsedMail<'_>) -> Option<String> {
+        if let Some(raw_disposition) = part.headers.get_first_value("Content-Disposition") {
+            let disposition = mailparse::parse_content_disposition(&raw_disposition);
+            if let Some(name) = disposition.params.get("filename") {
+                return Some(name.clone());
+            }
+        }
+        part.ctype.params.get("name").cloned()
+    }
 
-        if !parsed.subparts.is_empty() {
-            for part in &parsed.subparts {
-                let ctype = part.ctype.mimetype.to_ascii_lowercase();
-                if ctype == "text/plain" {
-                    let body = part.get_body().ok()?;
-                    let trimmed = body.trim().to_string();
-                    if !trimmed.is_empty() {
-                        return Some(trimmed);
-                    }
-                }
+    fn part_is_attachment(part: &mailparse::ParsedMail<'_>) -> bool {
+        let explicitly_attachment = part
+            .headers
+            .get_first_value("Content-Disposition")
+            .map(|raw| {
+                mailparse::parse_content_disposition(&raw).disposition
+                    == mailparse::DispositionType::Attachment
+            })
+            .unwrap_or(false);
+
+        explicitly_attachment || Self::part_filename(part).is_some()
+    }
+
+    /// Recurse into `multipart/mixed`, `multipart/related`, and
+    /// `multipart/alternative` trees, collecting the first `text/plain`
+    /// and `text/html` bodies we see plus every attachment part.
+    fn walk_mime_parts(part: &mailparse::ParsedMail<'_>, out: &mut MimeWalkResult) {
+        if Self::part_is_attachment(part) {
+            if let Ok(bytes) = part.get_body_raw() {
+                out.attachments.push(EmailAttachment {
+                    filename: Self::part_filename(part).unwrap_or_else(|| "attachment".into()),
+                    content_type: part.ctype.mimetype.clone(),
+                    bytes,
+                });
+            }
+            return;
+        }
+
+        if !part.subparts.is_empty() {
+            for sub in &part.subparts {
+                Self::walk_mime_parts(sub, out);
+            }
+            return;
+        }
+
+        let Ok(body) = part.get_body() else {
+            return;
+        };
+        let trimmed = body.trim();
+        if trimmed.is_empty() {
+            return;
+        }
+
+        match part.ctype.mimetype.to_ascii_lowercase().as_str() {
+            "text/plain" if out.plain_text.is_none() => {
+                out.plain_text = Some(trimmed.to_string());
+            }
+            "text/html" if out.html_text.is_none() => {
+                out.html_text = Some(trimmed.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Strip tags from an HTML body so it can stand in for plain text
+    /// when a message has no `text/plain` part at all.
+    fn strip_html_to_text(html: &str) -> String {
+        let mut text = String::with_capacity(html.len());
+        let mut in_tag = false;
+        for ch in html.chars() {
+            match ch {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                _ if !in_tag => text.push(ch),
+                _ => {}
             }
         }
 
-        let body = parsed.get_body().ok()?;
-        let trimmed = body.trim().to_string();
+        text.replace("&nbsp;", " ")
+            .replace("&amp;", "&")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+    }
+
+    fn parse_body_and_attachments(raw_email: &[u8]) -> Option<(String, Vec<EmailAttachment>)> {
+        let parsed = mailparse::parse_mail(raw_email).ok()?;
+
+        let mut walk = MimeWalkResult::default();
+        Self::walk_mime_parts(&parsed, &mut walk);
+
+        let text = walk
+            .plain_text
+            .or_else(|| walk.html_text.map(|html| Self::strip_html_to_text(&html)))?;
+
+        let trimmed = text.trim();
         if trimmed.is_empty() {
             None
         } else {
-            Some(trimmed)
+            Some((trimmed.to_string(), walk.attachments))
         }
     }
 
@@ -192,7 +523,7 @@ impl EmailChannel {
         html_out
     }
 
-    fn poll_unseen_blocking(&self) -> anyhow::Result<Vec<InboundEmail>> {
+    fn connect_session(&self) -> anyhow::Result<ImapSession> {
         if !self.imap_starttls {
             anyhow::bail!("imap_starttls=false is not supported in this build");
         }
@@ -203,15 +534,66 @@ impl EmailChannel {
             self.imap_host.as_str(),
             &tls,
         )?;
-        let mut session = client
-            .login(&self.imap_login, &self.imap_password)
-            .map_err(|(err, _)| anyhow::anyhow!("IMAP login failed: {err}"))?;
 
-        session.select(&self.inbox_folder)?;
-        let unseen = session.search("UNSEEN")?;
+        match &self.imap_auth {
+            AuthMethod::Password(password) => client
+                .login(&self.imap_login, password)
+                .map_err(|(err, _)| anyhow::anyhow!("IMAP login failed: {err}")),
+            AuthMethod::OAuth2 {
+                access_token_provider,
+            } => {
+                let access_token = Self::resolve_oauth_token_blocking(access_token_provider)?;
+                let authenticator = XOAuth2Authenticator {
+                    login: self.imap_login.clone(),
+                    access_token,
+                };
+                client
+                    .authenticate("XOAUTH2", &authenticator)
+                    .map_err(|(err, _)| anyhow::anyhow!("IMAP XOAUTH2 authentication failed: {err}"))
+            }
+        }
+    }
+
+    /// Run `f` against the shared, already-`SELECT`ed IMAP session,
+    /// connecting and selecting lazily on first use. Any error from `f` is
+    /// treated as a dropped socket: the cached session is discarded so the
+    /// *next* call reconnects and replays from a clean `Selected` state.
+    fn with_selected_session<R>(
+        &self,
+        f: impl FnOnce(&mut ImapSession) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        let mut guard = self
+            .imap_conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("IMAP connection lock poisoned"))?;
+
+        if matches!(guard.state, ImapConnState::Disconnected) {
+            let mut session = self.connect_session()?;
+            session.select(&self.inbox_folder)?;
+            guard.state = ImapConnState::Selected(session);
+        }
+
+        let ImapConnState::Selected(session) = &mut guard.state else {
+            unreachable!("just ensured Selected above");
+        };
+
+        match f(session) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                guard.state = ImapConnState::Disconnected;
+                Err(err)
+            }
+        }
+    }
+
+    fn fetch_and_flag_matching<T: std::io::Read + std::io::Write>(
+        session: &mut imap::Session<T>,
+        search_query: &str,
+    ) -> anyhow::Result<Vec<InboundEmail>> {
+        let matching = session.search(search_query)?;
 
         let mut out: Vec<InboundEmail> = Vec::new();
-        for uid in unseen {
+        for uid in matching {
             let seq = uid.to_string();
             let fetches = session.fetch(seq.as_str(), "RFC822")?;
 
@@ -228,7 +610,8 @@ impl EmailChannel {
                     continue;
                 };
 
-                let Some(content) = Self::parse_text_body(raw_email) else {
+                let Some((content, attachments)) = Self::parse_body_and_attachments(raw_email)
+                else {
                     continue;
                 };
 
@@ -242,53 +625,306 @@ impl EmailChannel {
                     sender,
                     content,
                     thread,
+                    attachments,
                 });
             }
 
             let _ = session.store(seq.as_str(), "+FLAGS (\\Seen)");
         }
 
-        let _ = session.logout();
         Ok(out)
     }
 
-    fn build_smtp_transport(&self) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
-        let creds = Credentials::new(self.smtp_login.clone(), self.smtp_password.clone());
+    /// Runs inside `tokio::task::spawn_blocking` on every poll cycle (see
+    /// `listen` below) — reusing the cached session via
+    /// `with_selected_session` only skips the login/`SELECT` handshake, it
+    /// does not avoid this per-cycle blocking hop. A true async IMAP
+    /// client would remove it; none is available in this tree.
+    fn poll_unseen_blocking(&self) -> anyhow::Result<Vec<InboundEmail>> {
+        let search_query = self.search_query.render()?;
+        self.with_selected_session(|session| Self::fetch_and_flag_matching(session, &search_query))
+    }
+
+    fn sieve_quote(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    /// Render a Sieve script that files mail from `allowed_senders` into
+    /// `inbox_folder` and discards everything else, so the decision is
+    /// enforced by the mail server before ZeroClaw ever polls.
+    fn render_allowlist_sieve_script(&self) -> String {
+        let inbox = Self::sieve_quote(&self.inbox_folder);
+
+        if self.allowed_senders.iter().any(|s| s == "*") {
+            return format!("require [\"fileinto\"];\nfileinto \"{inbox}\";\n");
+        }
+
+        if self.allowed_senders.is_empty() {
+            return "require [\"fileinto\"];\ndiscard;\n".to_string();
+        }
+
+        let conditions = self
+            .allowed_senders
+            .iter()
+            .map(|addr| format!("  address :is \"from\" \"{}\"", Self::sieve_quote(addr)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        format!(
+            "require [\"fileinto\"];\nif anyof (\n{conditions}\n) {{\n  fileinto \"{inbox}\";\n}} else {{\n  discard;\n}}\n"
+        )
+    }
+
+    fn managesieve_read_line(reader: &mut impl BufRead) -> anyhow::Result<String> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("ManageSieve connection closed unexpectedly");
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    /// Drain response lines (capability/response-code lines start with `"`
+    /// or `{`) until the terminal `OK`/`NO` status line.
+    fn managesieve_expect_ok(reader: &mut impl BufRead) -> anyhow::Result<()> {
+        loop {
+            let line = Self::managesieve_read_line(reader)?;
+            if line.starts_with("OK") {
+                return Ok(());
+            }
+            if line.starts_with("NO") {
+                anyhow::bail!("ManageSieve command failed: {line}");
+            }
+            // Otherwise a capability/string/literal continuation line; keep reading.
+        }
+    }
+
+    /// Connect to the configured ManageSieve host, upgrade to TLS via
+    /// `STARTTLS` (mandatory per RFC 5804, and the only way this repo's
+    /// other protocols — `connect_session`, `build_smtp_transport` — ever
+    /// send credentials), then upload+activate the allowlist-derived
+    /// script. Best-effort: callers decide whether a failure here should
+    /// block startup.
+    fn push_allowlist_script_blocking(&self) -> anyhow::Result<()> {
+        let Some(cfg) = &self.managesieve else {
+            return Ok(());
+        };
+
+        let script = self.render_allowlist_sieve_script();
+
+        let tcp = std::net::TcpStream::connect((cfg.host.as_str(), cfg.port))?;
+        let mut plain_reader = std::io::BufReader::new(tcp);
+
+        // The greeting is itself a capability response terminated by OK.
+        Self::managesieve_expect_ok(&mut plain_reader)?;
 
+        write!(plain_reader.get_mut(), "STARTTLS\r\n")?;
+        Self::managesieve_expect_ok(&mut plain_reader)?;
+
+        let tls = native_tls::TlsConnector::builder().build()?;
+        let tls_stream = tls.connect(cfg.host.as_str(), plain_reader.into_inner())?;
+        let mut reader = std::io::BufReader::new(tls_stream);
+
+        // STARTTLS re-advertises capabilities over the now-encrypted channel.
+        Self::managesieve_expect_ok(&mut reader)?;
+
+        let auth_plain = BASE64_STANDARD.encode(format!("\0{}\0{}", cfg.login, cfg.password));
+        write!(
+            reader.get_mut(),
+            "AUTHENTICATE \"PLAIN\" \"{auth_plain}\"\r\n"
+        )?;
+        Self::managesieve_expect_ok(&mut reader)?;
+
+        write!(
+            reader.get_mut(),
+            "PUTSCRIPT \"{MANAGESIEVE_SCRIPT_NAME}\" {{{}+}}\r\n{script}\r\n",
+            script.len()
+        )?;
+        Self::managesieve_expect_ok(&mut reader)?;
+
+        write!(
+            reader.get_mut(),
+            "SETACTIVE \"{MANAGESIEVE_SCRIPT_NAME}\"\r\n"
+        )?;
+        Self::managesieve_expect_ok(&mut reader)?;
+
+        Ok(())
+    }
+
+    /// Push the current allowlist to the mail server as an active Sieve
+    /// script. A no-op when ManageSieve isn't configured.
+    pub async fn sync_managesieve_allowlist(&self) -> anyhow::Result<()> {
+        if self.managesieve.is_none() {
+            return Ok(());
+        }
+
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.push_allowlist_script_blocking()).await?
+    }
+
+    /// Append a copy of a just-sent message to `sent_folder` over a
+    /// short-lived IMAP session, pre-marked `\Seen` since the user already
+    /// "read" their own reply. A no-op when no sent folder is configured.
+    fn append_sent_blocking(&self, raw_email: &[u8]) -> anyhow::Result<()> {
+        let Some(sent_folder) = &self.sent_folder else {
+            return Ok(());
+        };
+
+        let mut session = self.connect_session()?;
+        session.append_with_flags(sent_folder, raw_email, &[imap::types::Flag::Seen])?;
+        let _ = session.logout();
+        Ok(())
+    }
+
+    /// Hold a single IMAP session open, alternating IMAP IDLE waits with
+    /// fetches of the configured `search_query`, until `cancel` is set, the
+    /// receiver behind `tx` is dropped, or an IMAP call fails. Returns an
+    /// error up front when the server does not advertise `IDLE` support
+    /// (checked once, before the first wait). Each newly-fetched batch is pushed to
+    /// `tx` as soon as it's available, rather than waiting for the whole
+    /// session to end.
+    ///
+    /// Like `poll_unseen_blocking`, this entire function body still runs
+    /// inside a single `tokio::task::spawn_blocking` call per `listen`
+    /// session (see `listen` below) — session reuse removed the repeated
+    /// login/`SELECT` handshake, not the blocking-task hop itself.
+    fn idle_listen_blocking(
+        &self,
+        tx: &tokio::sync::mpsc::Sender<Vec<InboundEmail>>,
+        cancel: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let search_query = self.search_query.render()?;
+
+        let supports_idle = self.with_selected_session(|session| {
+            Ok(session
+                .capabilities()
+                .map(|caps| caps.has_str("IDLE"))
+                .unwrap_or(false))
+        })?;
+
+        if !supports_idle {
+            anyhow::bail!("IMAP server does not advertise IDLE support");
+        }
+
+        while !cancel.load(Ordering::Relaxed) {
+            self.with_selected_session(|session| {
+                let mut idle = session.idle();
+                idle.set_keepalive(IDLE_RENEW_INTERVAL);
+                idle.wait_keepalive()?;
+                Ok(())
+            })?;
+
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let batch = self
+                .with_selected_session(|session| Self::fetch_and_flag_matching(session, &search_query))?;
+            if !batch.is_empty() && tx.blocking_send(batch).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn build_smtp_transport(&self) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
         let mut transport_builder = if self.smtp_starttls {
             AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_host)?
         } else {
             AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_host)?
         };
+        transport_builder = transport_builder.port(self.smtp_port);
+
+        transport_builder = match &self.smtp_auth {
+            AuthMethod::Password(password) => {
+                let creds = Credentials::new(self.smtp_login.clone(), password.clone());
+                transport_builder.credentials(creds)
+            }
+            AuthMethod::OAuth2 {
+                access_token_provider,
+            } => {
+                let access_token = Self::resolve_oauth_token(access_token_provider).await?;
+                let creds = Credentials::new(self.smtp_login.clone(), access_token);
+                transport_builder
+                    .credentials(creds)
+                    .authentication(vec![Mechanism::Xoauth2])
+            }
+        };
 
-        transport_builder = transport_builder.port(self.smtp_port).credentials(creds);
         Ok(transport_builder.build())
     }
 
     async fn check_imap_connectivity(&self) -> bool {
         let this = self.clone();
-        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
-            if !this.imap_starttls {
-                anyhow::bail!("imap_starttls=false is not supported in this build");
-            }
-
-            let tls = native_tls::TlsConnector::builder().build()?;
-            let client = imap::connect(
-                (this.imap_host.as_str(), this.imap_port),
-                this.imap_host.as_str(),
-                &tls,
-            )?;
-            let mut session = client
-                .login(&this.imap_login, &this.imap_password)
-                .map_err(|(err, _)| anyhow::anyhow!("IMAP login failed: {err}"))?;
+        let result = tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut session = this.connect_session()?;
             session.select(&this.inbox_folder)?;
             let _ = session.logout();
             Ok(())
         })
-        .await
-        .ok()
-        .and_then(Result::ok)
-        .is_some()
+        .await;
+
+        match result {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                tracing::warn!("Email IMAP connectivity/auth check failed: {e}");
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Email IMAP connectivity check task join error: {e}");
+                false
+            }
+        }
+    }
+}
+
+impl EmailChannel {
+    /// Dedup, filter, and forward a batch of freshly-fetched messages to
+    /// `tx`. Returns `false` once the receiver has gone away, signalling
+    /// that `listen` should stop.
+    async fn dispatch_batch(
+        messages: Vec<InboundEmail>,
+        seen_ids: &mut HashSet<String>,
+        this: &Self,
+        tx: &tokio::sync::mpsc::Sender<ChannelMessage>,
+    ) -> bool {
+        for inbound in messages {
+            let mut id = inbound.uid.clone();
+            if let Some(meta) = Self::encode_thread_meta(&inbound.thread) {
+                id = format!("{}{}{}", inbound.uid, EMAIL_REPLY_META_SEP, meta);
+            }
+
+            if seen_ids.contains(&id) {
+                continue;
+            }
+            seen_ids.insert(id.clone());
+
+            if !this.is_sender_allowed(&inbound.sender) {
+                tracing::warn!(
+                    "Email: ignoring message from unauthorized sender: {}",
+                    inbound.sender
+                );
+                continue;
+            }
+
+            let channel_msg = ChannelMessage {
+                id,
+                sender: inbound.sender,
+                content: inbound.content,
+                channel: "email".to_string(),
+                attachments: inbound.attachments,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            };
+
+            if tx.send(channel_msg).await.is_err() {
+                return false;
+            }
+        }
+
+        true
     }
 }
 
@@ -299,6 +935,18 @@ impl Channel for EmailChannel {
     }
 
     async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()> {
+        self.send_with_attachments(message, recipient, &[]).await
+    }
+
+    /// Builds a `multipart/mixed` envelope around the usual plain/HTML
+    /// alternative body when there are attachments, or just the
+    /// alternative body when there aren't.
+    async fn send_with_attachments(
+        &self,
+        message: &str,
+        recipient: &str,
+        attachments: &[EmailAttachment],
+    ) -> anyhow::Result<()> {
         let (recipient_email, thread_meta) = Self::parse_recipient_and_thread_meta(recipient);
 
         if !Self::validate_email_identity(&self.from_address) {
@@ -325,69 +973,110 @@ impl Channel for EmailChannel {
         }
 
         let html_body = Self::markdown_to_html(message);
-        let email = builder.multipart(MultiPart::alternative_plain_html(
-            message.to_string(),
-            html_body,
-        ))?;
+        let alternative_body =
+            MultiPart::alternative_plain_html(message.to_string(), html_body);
 
-        let transport = self.build_smtp_transport()?;
+        let body = if attachments.is_empty() {
+            alternative_body
+        } else {
+            let mut mixed = MultiPart::mixed().multipart(alternative_body);
+            for attachment in attachments {
+                let content_type = attachment
+                    .content_type
+                    .parse::<ContentType>()
+                    .unwrap_or(ContentType::TEXT_PLAIN);
+                mixed = mixed.singlepart(
+                    Attachment::new(attachment.filename.clone())
+                        .body(attachment.bytes.clone(), content_type),
+                );
+            }
+            mixed
+        };
+
+        let email = builder.multipart(body)?;
+        let raw_email = email.formatted();
+
+        let transport = self.build_smtp_transport().await?;
         transport.send(email).await?;
+
+        if self.sent_folder.is_some() {
+            let this = self.clone();
+            match tokio::task::spawn_blocking(move || this.append_sent_blocking(&raw_email)).await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    tracing::warn!("Email: failed to append sent copy to Sent folder: {e}")
+                }
+                Err(e) => tracing::warn!("Email: sent-folder append task join error: {e}"),
+            }
+        }
+
         Ok(())
     }
 
     async fn listen(&self, tx: tokio::sync::mpsc::Sender<ChannelMessage>) -> anyhow::Result<()> {
-        tracing::info!("Email channel listening on folder {}...", self.inbox_folder);
+        tracing::info!(
+            "Email channel listening on folder {} (mode: {:?})...",
+            self.inbox_folder,
+            self.poll_mode
+        );
+
+        if let Err(e) = self.sync_managesieve_allowlist().await {
+            tracing::warn!("Email: failed to push ManageSieve allowlist script: {e}");
+        }
 
         let poll_every = std::time::Duration::from_secs(self.poll_interval_secs.max(5));
         let mut seen_ids = HashSet::new();
+        let mut use_idle = self.poll_mode == EmailPollMode::Idle;
 
-        loop {
-            let this = self.clone();
-            let result = tokio::task::spawn_blocking(move || this.poll_unseen_blocking()).await;
-
-            match result {
-                Ok(Ok(messages)) => {
-                    for inbound in messages {
-                        let mut id = inbound.uid.clone();
-                        if let Some(meta) = Self::encode_thread_meta(&inbound.thread) {
-                            id = format!("{}{}{}", inbound.uid, EMAIL_REPLY_META_SEP, meta);
-                        }
-
-                        if seen_ids.contains(&id) {
-                            continue;
-                        }
-                        seen_ids.insert(id.clone());
-
-                        if !self.is_sender_allowed(&inbound.sender) {
-                            tracing::warn!(
-                                "Email: ignoring message from unauthorized sender: {}",
-                                inbound.sender
-                            );
-                            continue;
-                        }
-
-                        let channel_msg = ChannelMessage {
-                            id,
-                            sender: inbound.sender,
-                            content: inbound.content,
-                            channel: "email".to_string(),
-                            timestamp: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs(),
-                        };
-
-                        if tx.send(channel_msg).await.is_err() {
-                            return Ok(());
-                        }
+        'sessions: loop {
+            if use_idle {
+                let this = self.clone();
+                let cancel = Arc::new(AtomicBool::new(false));
+                let cancel_for_task = cancel.clone();
+                let (batch_tx, mut batch_rx) = tokio::sync::mpsc::channel::<Vec<InboundEmail>>(4);
+
+                let session_task = tokio::task::spawn_blocking(move || {
+                    this.idle_listen_blocking(&batch_tx, &cancel_for_task)
+                });
+
+                while let Some(batch) = batch_rx.recv().await {
+                    if !Self::dispatch_batch(batch, &mut seen_ids, self, &tx).await {
+                        cancel.store(true, Ordering::Relaxed);
+                        let _ = session_task.await;
+                        return Ok(());
                     }
                 }
+
+                match session_task.await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        tracing::warn!("Email IDLE session error, falling back to polling: {e}");
+                        use_idle = false;
+                    }
+                    Err(e) => tracing::warn!("Email IDLE task join error: {e}"),
+                }
+
+                continue 'sessions;
+            }
+
+            let this = self.clone();
+            let messages = match tokio::task::spawn_blocking(move || this.poll_unseen_blocking())
+                .await
+            {
+                Ok(Ok(messages)) => messages,
                 Ok(Err(e)) => {
                     tracing::warn!("Email poll error: {e}");
+                    Vec::new()
                 }
                 Err(e) => {
                     tracing::warn!("Email poll task join error: {e}");
+                    Vec::new()
                 }
+            };
+
+            if !Self::dispatch_batch(messages, &mut seen_ids, self, &tx).await {
+                return Ok(());
             }
 
             tokio::time::sleep(poll_every).await;
@@ -403,11 +1092,21 @@ impl Channel for EmailChannel {
             return false;
         }
 
-        let Ok(transport) = self.build_smtp_transport() else {
-            return false;
+        let transport = match self.build_smtp_transport().await {
+            Ok(transport) => transport,
+            Err(e) => {
+                tracing::warn!("Email SMTP transport/auth setup failed: {e}");
+                return false;
+            }
         };
 
-        transport.test_connection().await.unwrap_or(false)
+        match transport.test_connection().await {
+            Ok(ok) => ok,
+            Err(e) => {
+                tracing::warn!("Email SMTP connectivity check failed: {e}");
+                false
+            }
+        }
     }
 }
 
@@ -420,17 +1119,21 @@ mod tests {
             "imap.example.com".into(),
             993,
             "imap-user".into(),
-            "imap-pass".into(),
+            AuthMethod::Password("imap-pass".into()),
             true,
             "smtp.example.com".into(),
             587,
             "smtp-user".into(),
-            "smtp-pass".into(),
+            AuthMethod::Password("smtp-pass".into()),
             true,
             "bot@example.com".into(),
             "INBOX".into(),
+            None,
             10,
+            EmailPollMode::Poll,
             allowed_senders,
+            None,
+            SearchCriterion::Unseen,
         )
     }
 
@@ -440,6 +1143,22 @@ mod tests {
         assert_eq!(ch.name(), "email");
     }
 
+    #[test]
+    fn xoauth2_process_returns_unencoded_sasl_response() {
+        let authenticator = XOAuth2Authenticator {
+            login: "alice@example.com".into(),
+            access_token: "ya29.token".into(),
+        };
+        let response = authenticator.process(b"");
+        assert_eq!(
+            response,
+            "user=alice@example.com\x01auth=Bearer ya29.token\x01\x01"
+        );
+        // The imap crate base64-encodes this for us; returning already-encoded
+        // bytes here would double-encode and break every real server login.
+        assert!(BASE64_STANDARD.decode(&response).is_err());
+    }
+
     #[test]
     fn wildcard_sender_allowed() {
         let ch = make_channel(vec!["*".into()]);
@@ -483,10 +1202,28 @@ mod tests {
     }
 
     #[test]
-    fn parse_text_body_prefers_text_plain() {
+    fn parse_body_prefers_text_plain() {
         let raw = b"From: a@example.com\r\nTo: b@example.com\r\nSubject: T\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nhello world\r\n";
-        let body = EmailChannel::parse_text_body(raw);
-        assert_eq!(body.as_deref(), Some("hello world"));
+        let (body, attachments) = EmailChannel::parse_body_and_attachments(raw).expect("body");
+        assert_eq!(body, "hello world");
+        assert!(attachments.is_empty());
+    }
+
+    #[test]
+    fn parse_body_falls_back_to_html_when_no_plain_part() {
+        let raw = b"From: a@example.com\r\nTo: b@example.com\r\nSubject: T\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>hello <b>world</b></p>\r\n";
+        let (body, _attachments) = EmailChannel::parse_body_and_attachments(raw).expect("body");
+        assert_eq!(body, "hello world");
+    }
+
+    #[test]
+    fn parse_body_collects_attachment_parts() {
+        let raw = b"From: a@example.com\r\nTo: b@example.com\r\nSubject: T\r\nContent-Type: multipart/mixed; boundary=X\r\n\r\n--X\r\nContent-Type: text/plain\r\n\r\nsee attached\r\n--X\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"report.pdf\"\r\n\r\n%PDF-1.4\r\n--X--\r\n";
+        let (body, attachments) = EmailChannel::parse_body_and_attachments(raw).expect("body");
+        assert_eq!(body, "see attached");
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename, "report.pdf");
+        assert_eq!(attachments[0].content_type, "application/pdf");
     }
 
     #[test]
@@ -536,4 +1273,107 @@ mod tests {
         assert!(html.contains("<h1>Titulo</h1>"));
         assert!(html.contains("<strong>negrito</strong>"));
     }
+
+    #[test]
+    fn sieve_script_allows_wildcard_without_conditions() {
+        let ch = make_channel(vec!["*".into()]);
+        let script = ch.render_allowlist_sieve_script();
+        assert!(script.contains("fileinto \"INBOX\";"));
+        assert!(!script.contains("anyof"));
+    }
+
+    #[test]
+    fn sieve_script_discards_when_allowlist_empty() {
+        let ch = make_channel(vec![]);
+        let script = ch.render_allowlist_sieve_script();
+        assert_eq!(script, "require [\"fileinto\"];\ndiscard;\n");
+    }
+
+    #[test]
+    fn sieve_script_files_allowed_senders_and_discards_rest() {
+        let ch = make_channel(vec!["alice@example.com".into(), "bob@example.com".into()]);
+        let script = ch.render_allowlist_sieve_script();
+        assert!(script.contains("address :is \"from\" \"alice@example.com\""));
+        assert!(script.contains("address :is \"from\" \"bob@example.com\""));
+        assert!(script.contains("fileinto \"INBOX\";"));
+        assert!(script.contains("discard;"));
+    }
+
+    #[test]
+    fn search_criterion_renders_simple_atoms() {
+        assert_eq!(SearchCriterion::Unseen.render().unwrap(), "UNSEEN");
+        assert_eq!(SearchCriterion::Unflagged.render().unwrap(), "UNFLAGGED");
+        assert_eq!(
+            SearchCriterion::Since("01-Jan-2024".into()).render().unwrap(),
+            "SINCE 01-Jan-2024"
+        );
+        assert_eq!(
+            SearchCriterion::From("alice@example.com".into())
+                .render()
+                .unwrap(),
+            "FROM \"alice@example.com\""
+        );
+        assert_eq!(
+            SearchCriterion::Subject("invoice".into()).render().unwrap(),
+            "SUBJECT \"invoice\""
+        );
+    }
+
+    #[test]
+    fn search_criterion_renders_and_or_not_composition() {
+        let query = SearchCriterion::And(vec![
+            SearchCriterion::Unseen,
+            SearchCriterion::Not(Box::new(SearchCriterion::Unflagged)),
+            SearchCriterion::Or(
+                Box::new(SearchCriterion::From("alice@example.com".into())),
+                Box::new(SearchCriterion::From("bob@example.com".into())),
+            ),
+        ]);
+        assert_eq!(
+            query.render().unwrap(),
+            "UNSEEN NOT UNFLAGGED OR FROM \"alice@example.com\" FROM \"bob@example.com\""
+        );
+    }
+
+    #[test]
+    fn search_criterion_parenthesizes_and_nested_under_not_or_or() {
+        let query = SearchCriterion::Not(Box::new(SearchCriterion::And(vec![
+            SearchCriterion::Unseen,
+            SearchCriterion::Unflagged,
+        ])));
+        assert_eq!(query.render().unwrap(), "NOT (UNSEEN UNFLAGGED)");
+
+        let query = SearchCriterion::Or(
+            Box::new(SearchCriterion::And(vec![
+                SearchCriterion::Unseen,
+                SearchCriterion::Unflagged,
+            ])),
+            Box::new(SearchCriterion::From("alice@example.com".into())),
+        );
+        assert_eq!(
+            query.render().unwrap(),
+            "OR (UNSEEN UNFLAGGED) FROM \"alice@example.com\""
+        );
+    }
+
+    #[test]
+    fn search_criterion_rejects_empty_and() {
+        assert!(SearchCriterion::And(vec![]).render().is_err());
+    }
+
+    #[test]
+    fn search_criterion_rejects_values_that_could_smuggle_search_keys() {
+        assert!(SearchCriterion::From("x\" OR FROM \"y".into()).render().is_err());
+        assert!(SearchCriterion::Subject("line1\r\nline2".into())
+            .render()
+            .is_err());
+        assert!(SearchCriterion::Since("  ".into()).render().is_err());
+    }
+
+    #[test]
+    fn search_criterion_rejects_since_with_embedded_search_key() {
+        assert!(SearchCriterion::Since("01-Jan-2024 UNFLAGGED".into())
+            .render()
+            .is_err());
+    }
 }
@@ -0,0 +1,41 @@
+use crate::channels::email::EmailAttachment;
+use async_trait::async_trait;
+
+/// A single inbound or outbound message routed through a `Channel`.
+#[derive(Debug, Clone)]
+pub struct ChannelMessage {
+    pub id: String,
+    pub sender: String,
+    pub content: String,
+    pub channel: String,
+    pub attachments: Vec<EmailAttachment>,
+    pub timestamp: u64,
+}
+
+/// A transport a bot can send messages through and receive them from
+/// (email, chat, etc). Implementations are held as `dyn Channel` by the
+/// bot's dispatch path, so new capabilities need a trait method (with a
+/// sensible default) rather than an inherent method a caller can only
+/// reach by already holding the concrete type.
+#[async_trait]
+pub trait Channel: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()>;
+
+    /// Like `send`, but with outbound files attached. Defaults to ignoring
+    /// attachments and falling back to `send`, so channels that don't
+    /// support attachments don't need to implement this.
+    async fn send_with_attachments(
+        &self,
+        message: &str,
+        recipient: &str,
+        _attachments: &[EmailAttachment],
+    ) -> anyhow::Result<()> {
+        self.send(message, recipient).await
+    }
+
+    async fn listen(&self, tx: tokio::sync::mpsc::Sender<ChannelMessage>) -> anyhow::Result<()>;
+
+    async fn health_check(&self) -> bool;
+}